@@ -17,6 +17,9 @@ extern crate log4rs;
 
 extern crate env_logger;
 
+mod script;
+mod sequence;
+mod socket;
 mod util;
 
 use std::io::{self, Write};
@@ -36,17 +39,33 @@ use log4rs::append::file::FileAppender;
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::config::{Appender, Config, Root};
 use log::LevelFilter;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
-use util::event::{Event, Events};
+use script::Scripting;
+use util::event::{redraw_channel, CancellationToken, Event, EventStatus, Events, Renderer};
+
+/// What to load into `$VISUAL`/`$EDITOR`, set by `handle_event` and
+/// consumed by the render loop, the only thread allowed to touch the terminal.
+enum EditTarget {
+    /// Edit `app.input` in place.
+    Input,
+    /// Edit the most recent message; the result is pushed as a new one.
+    LastMessage,
+}
 
 /// App holds the state of the application
+#[derive(Clone)]
 struct App {
     /// Current value of the input box
     input: Arc<Mutex<String>>,
     /// History of recorded messages
     messages: Arc<Mutex<Vec<String>>>,
+    /// Set by `app:quit()` from a Lua keybinding
+    quit_requested: Arc<AtomicBool>,
+    /// Set when a keybinding asks to suspend into `$VISUAL`/`$EDITOR`
+    edit_requested: Arc<Mutex<Option<EditTarget>>>,
 }
 
 impl Default for App {
@@ -54,11 +73,143 @@ impl Default for App {
         App {
             input: Arc::new(Mutex::new(String::new())),
             messages: Arc::new(Mutex::new(Vec::new())),
+            quit_requested: Arc::new(AtomicBool::new(false)),
+            edit_requested: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// Maps a pressed key to the name a Lua config script would `bind` it under.
+fn key_binding_name(key: &Key) -> Option<String> {
+    match key {
+        Key::Char('\n') => Some("enter".to_string()),
+        Key::Char(c) => Some(c.to_string()),
+        Key::Backspace => Some("backspace".to_string()),
+        Key::Esc => Some("esc".to_string()),
+        _ => None,
+    }
+}
+
+/// Central dispatch point for every `Event`, whatever produced it.
+fn handle_event(app: &App, scripting: &Mutex<Scripting>, event: Event) -> EventStatus {
+    if let Event::Input(key) = &event {
+        if let Some(name) = key_binding_name(key) {
+            match scripting.lock().unwrap().dispatch_key(&name) {
+                Ok(true) => {
+                    return if app.quit_requested.load(Ordering::SeqCst) {
+                        EventStatus::Terminate
+                    } else {
+                        EventStatus::Finished
+                    };
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    error!("lua keybinding for {:?} failed: {}", name, err);
+                }
+            }
+        }
+    }
+
+    match event {
+        Event::Input(Key::Char('q')) => EventStatus::Terminate,
+        Event::Input(Key::Ctrl('e')) => {
+            *app.edit_requested.lock().unwrap() = Some(EditTarget::Input);
+            EventStatus::Finished
+        }
+        Event::Input(Key::Ctrl('r')) => {
+            *app.edit_requested.lock().unwrap() = Some(EditTarget::LastMessage);
+            EventStatus::Finished
+        }
+        Event::Input(Key::Char('\n')) => {
+            let mut input = app.input.lock().unwrap();
+            let mut messages = app.messages.lock().unwrap();
+            messages.push(input.drain(..).collect());
+            info!("{}", input);
+            EventStatus::Finished
+        }
+        Event::Input(Key::Char(c)) => {
+            let mut input = app.input.lock().unwrap();
+            input.push(c);
+            info!("{}", input);
+            EventStatus::Finished
+        }
+        Event::Input(Key::Backspace) => {
+            let mut input = app.input.lock().unwrap();
+            input.pop();
+            info!("{}", input);
+            EventStatus::Finished
+        }
+        Event::Input(_) => EventStatus::Ok,
+        Event::App(_) => EventStatus::Ok,
+    }
+}
+
+/// Reads input on its own thread and feeds each key through `handle_event`.
+///
+/// Polls with a timeout rather than blocking indefinitely on `stdin`, so
+/// a `cancel` set by another thread (a remote "quit" over the socket, or
+/// a startup sequence) is noticed even if no one ever presses a key.
+fn spawn_input_thread(
+    app: App,
+    scripting: Arc<Mutex<Scripting>>,
+    renderer: Renderer,
+    cancel: CancellationToken,
+    pause: CancellationToken,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let events = Events::new();
+        loop {
+            if cancel.is_cancelled() || pause.is_cancelled() {
+                return;
+            }
+
+            let key = match events.next_timeout() {
+                Ok(key) => key,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            };
+
+            match handle_event(&app, &scripting, Event::Input(key)) {
+                EventStatus::Ok => {}
+                EventStatus::Finished => renderer.request_redraw(),
+                EventStatus::Terminate => {
+                    cancel.cancel();
+                    renderer.request_redraw();
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// Replays a `Sequence`'s keystrokes through the same dispatcher real input uses.
+fn run_sequence(
+    app: &App,
+    scripting: &Mutex<Scripting>,
+    renderer: &Renderer,
+    cancel: &CancellationToken,
+    sequence: &sequence::Sequence,
+) {
+    for command in sequence.commands() {
+        for key in command.keys() {
+            match handle_event(app, scripting, Event::Input(key)) {
+                EventStatus::Ok => {}
+                EventStatus::Finished => renderer.request_redraw(),
+                EventStatus::Terminate => {
+                    cancel.cancel();
+                    renderer.request_redraw();
+                    return;
+                }
+            }
         }
     }
 }
 
-fn render(app: App) -> Result<(), failure::Error> {
+fn render(
+    app: App,
+    scripting: Arc<Mutex<Scripting>>,
+    startup_sequence: Option<sequence::Sequence>,
+) -> Result<(), failure::Error> {
     // Terminal initialization
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = MouseTerminal::from(stdout);
@@ -66,80 +217,199 @@ fn render(app: App) -> Result<(), failure::Error> {
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    // The input thread pushes onto this channel whenever app state changes;
+    // the render loop parks here instead of polling for input itself.
+    let (renderer, redraw) = redraw_channel();
+    let cancel = CancellationToken::new();
+
+    if let Some(sequence) = &startup_sequence {
+        run_sequence(&app, &scripting, &renderer, &cancel, sequence);
+    }
+
+    // Runs detached: it serves connections for the life of the process and
+    // is torn down implicitly on exit rather than joined.
+    let socket_path = socket_path();
+    let _socket_thread = socket::spawn_socket_server(
+        &socket_path,
+        app.clone(),
+        scripting.clone(),
+        renderer.clone(),
+        cancel.clone(),
+    )
+    .map_err(|err| warn!("remote control socket {:?} unavailable: {}", socket_path, err))
+    .ok();
+
+    let mut input_pause = CancellationToken::new();
+    let mut input_thread = spawn_input_thread(
+        app.clone(),
+        scripting.clone(),
+        renderer.clone(),
+        cancel.clone(),
+        input_pause.clone(),
+    );
+
+    // Draw the initial state (including anything a startup sequence typed)
+    // before waiting on the first signal.
+    draw(&mut terminal, &app)?;
 
-    // Setup event handlers
-    let events = Events::new();
     loop {
-        // Draw UI
-        {
-            let app_input_clone = app.input.clone();
-            let mut app_input = app_input_clone.lock().unwrap();
-
-            let app_messages_clone = app.messages.clone();
-            let mut app_messages = app_messages_clone.lock().unwrap();
-
-            terminal.draw(|mut f| {
-
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .margin(2)
-                    .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
-                    .split(f.size());
-
-                Paragraph::new([Text::raw(&*app_input)].iter())
-                    .style(Style::default().fg(Color::Yellow))
-                    .block(Block::default().borders(Borders::ALL).title("Input"))
-                    .render(&mut f, chunks[0]);
-
-                let messages = app_messages
-                    .iter()
-                    .enumerate()
-                    .map(|(i, m)| Text::raw(format!("{}: {}", i, m)));
-
-                List::new(messages)
-                    .block(Block::default().borders(Borders::ALL).title("Messages"))
-                    .render(&mut f, chunks[1]);
-            })?;
+        redraw.wait()?;
+        draw(&mut terminal, &app)?;
+
+        if let Some(target) = app.edit_requested.lock().unwrap().take() {
+            // Stop the background stdin reader before the editor takes over
+            // the tty, so the two don't race for the same keystrokes, then
+            // start a fresh one once we get the tty back.
+            input_pause.cancel();
+            input_thread.join().expect("input thread panicked");
+
+            terminal = suspend_and_edit(terminal, &app, target)?;
+            draw(&mut terminal, &app)?;
+
+            input_pause = CancellationToken::new();
+            input_thread = spawn_input_thread(
+                app.clone(),
+                scripting.clone(),
+                renderer.clone(),
+                cancel.clone(),
+                input_pause.clone(),
+            );
         }
 
-        {
-            let app_input_clone = app.input.clone();
-            let mut app_input = app_input_clone.lock().unwrap();
-
-            let app_messages_clone = app.messages.clone();
-            let mut app_messages = app_messages_clone.lock().unwrap();
-
-            // Put the cursor back inside the input box
-            write!(
-                terminal.backend_mut(),
-                "{}",
-                Goto(4 + app_input.width() as u16, 4)
-            )?;
-
-            // Handle input
-            match events.next()? {
-                Event::Input(input) => match input {
-                    Key::Char('q') => {
-                        break;
-                    }
-                    Key::Char('\n') => {
-                        app_messages.push(app_input.drain(..).collect());
-                    }
-                    Key::Char(c) => {
-                        app_input.push(c);
-                    }
-                    Key::Backspace => {
-                        app_input.pop();
-                    }
-                    _ => {}
-                },
-                _ => {}
-            }
-            info!("{}", app_input);
+        if cancel.is_cancelled() {
+            break;
         }
+    }
+
+    // Drop the terminal first so raw mode and the alternate screen are torn
+    // down before we join the input thread. The input thread polls `cancel`
+    // itself, so it exits promptly regardless of which thread set it.
+    drop(terminal);
+    input_pause.cancel();
+    input_thread.join().expect("input thread panicked");
+
+    Ok(())
+}
+
+type Backend = TermionBackend<AlternateScreen<MouseTerminal<termion::raw::RawTerminal<io::Stdout>>>>;
+
+/// Leaves raw mode and the alternate screen to run the editor, then
+/// rebuilds the terminal stack once it exits.
+fn suspend_and_edit(
+    terminal: Terminal<Backend>,
+    app: &App,
+    target: EditTarget,
+) -> Result<Terminal<Backend>, failure::Error> {
+    let initial = match &target {
+        EditTarget::Input => app.input.lock().unwrap().clone(),
+        EditTarget::LastMessage => app
+            .messages
+            .lock()
+            .unwrap()
+            .last()
+            .cloned()
+            .unwrap_or_default(),
+    };
+
+    // Dropping `terminal` restores the cooked/normal screen so the editor
+    // owns the real TTY, not the alternate screen `super-cd` draws into.
+    drop(terminal);
+
+    // A failed editor (non-zero exit, launch failure, ...) shouldn't take
+    // the whole session down with it; log it and leave the app state as-is.
+    match run_editor(&initial) {
+        Ok(edited) => match target {
+            EditTarget::Input => *app.input.lock().unwrap() = edited,
+            EditTarget::LastMessage => app.messages.lock().unwrap().push(edited),
+        },
+        Err(err) => warn!("editor session failed: {}", err),
+    }
 
-        
+    let stdout = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(stdout);
+    let stdout = AlternateScreen::from(stdout);
+    let backend = TermionBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    // The screen was just handed back to us from the editor; nothing on
+    // it matches our state, so force a full repaint.
+    terminal.clear()?;
+    Ok(terminal)
+}
+
+/// Writes `initial` to a scratch file, opens it in `$VISUAL`/`$EDITOR`
+/// (falling back to `vi`), and returns the file's contents afterward.
+fn run_editor(initial: &str) -> Result<String, failure::Error> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let path = std::env::temp_dir().join(format!("super-cd-edit-{}.txt", std::process::id()));
+    std::fs::write(&path, initial)?;
+
+    // $VISUAL/$EDITOR commonly carry arguments (`"code --wait"`,
+    // `"emacsclient -c"`), so split on whitespace rather than treating the
+    // whole string as a single binary name.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(&editor);
+
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .stdin(tty.try_clone()?)
+        .stdout(tty.try_clone()?)
+        .stderr(tty)
+        .status()?;
+
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    if !status.success() {
+        return Err(failure::err_msg(format!(
+            "editor `{}` exited with {}",
+            editor, status
+        )));
     }
+
+    Ok(edited?.trim_end_matches('\n').to_string())
+}
+
+fn draw(terminal: &mut Terminal<Backend>, app: &App) -> Result<(), failure::Error> {
+    let app_input = app.input.lock().unwrap();
+    let app_messages = app.messages.lock().unwrap();
+
+    terminal.draw(|mut f| {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(2)
+            .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+            .split(f.size());
+
+        Paragraph::new([Text::raw(&*app_input)].iter())
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title("Input"))
+            .render(&mut f, chunks[0]);
+
+        let messages = app_messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| Text::raw(format!("{}: {}", i, m)));
+
+        List::new(messages)
+            .block(Block::default().borders(Borders::ALL).title("Messages"))
+            .render(&mut f, chunks[1]);
+    })?;
+
+    // Put the cursor back inside the input box
+    write!(
+        terminal.backend_mut(),
+        "{}",
+        Goto(4 + app_input.width() as u16, 4)
+    )?;
+
     Ok(())
 }
 
@@ -158,11 +428,47 @@ fn main() -> Result<(), failure::Error> {
 
     log4rs::init_config(config)?;
 
-
     // Create default app state
-    let mut app = App::default();
+    let app = App::default();
+
+    // Load the user's config script, if any, so it can register
+    // keybindings before the first key is ever read.
+    let scripting = Scripting::new(app.clone())?;
+    if let Some(path) = config_path() {
+        if path.exists() {
+            scripting.load_config(&path)?;
+        }
+    }
 
-    render(app);
+    render(app, Arc::new(Mutex::new(scripting)), startup_sequence())?;
 
     Ok(())
 }
+
+/// `$SUPER_CD_CONFIG` if set, otherwise `~/.config/super-cd/init.lua`.
+fn config_path() -> Option<std::path::PathBuf> {
+    if let Some(path) = std::env::var_os("SUPER_CD_CONFIG") {
+        return Some(std::path::PathBuf::from(path));
+    }
+    std::env::var_os("HOME")
+        .map(|home| std::path::Path::new(&home).join(".config/super-cd/init.lua"))
+}
+
+/// A `--sequence "type foo; enter"` startup flag, run once before interactive mode.
+fn startup_sequence() -> Option<sequence::Sequence> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--sequence" {
+            return args.next().map(sequence::Sequence::new);
+        }
+    }
+    None
+}
+
+///// `$SUPER_CD_SOCKET` if set, otherwise `super-cd-<pid>.sock` in `std::env::temp_dir()`.
+fn socket_path() -> std::path::PathBuf {
+    if let Some(path) = std::env::var_os("SUPER_CD_SOCKET") {
+        return std::path::PathBuf::from(path);
+    }
+    std::env::temp_dir().join(format!("super-cd-{}.sock", std::process::id()))
+}
@@ -0,0 +1,73 @@
+//! Embeds a Lua runtime for scriptable keybindings.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use mlua::{Function, Lua, Table, UserData, UserDataMethods};
+
+use crate::App;
+
+/// Exposes `App` to Lua as userdata.
+struct LuaApp(App);
+
+impl UserData for LuaApp {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get_input", |_, this, ()| {
+            Ok(this.0.input.lock().unwrap().clone())
+        });
+        methods.add_method("set_input", |_, this, value: String| {
+            *this.0.input.lock().unwrap() = value;
+            Ok(())
+        });
+        methods.add_method("push_message", |_, this, value: String| {
+            this.0.messages.lock().unwrap().push(value);
+            Ok(())
+        });
+        methods.add_method("quit", |_, this, ()| {
+            this.0.quit_requested.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+    }
+}
+
+/// A Lua runtime bound to one `App`, with keybindings populated by `bind`.
+pub struct Scripting {
+    lua: Lua,
+}
+
+impl Scripting {
+    pub fn new(app: App) -> mlua::Result<Scripting> {
+        let lua = Lua::new();
+        lua.globals().set("app", LuaApp(app))?;
+
+        let bindings = lua.create_table()?;
+        lua.globals().set("__keybindings", bindings)?;
+
+        let bind = lua.create_function(|lua, (key, callback): (String, Function)| {
+            let bindings: Table = lua.globals().get("__keybindings")?;
+            bindings.set(key, callback)
+        })?;
+        lua.globals().set("bind", bind)?;
+
+        Ok(Scripting { lua })
+    }
+
+    pub fn load_config(&self, path: &Path) -> mlua::Result<()> {
+        let source = fs::read_to_string(path).map_err(mlua::Error::external)?;
+        self.lua.load(&source).set_name(path.to_string_lossy()).exec()
+    }
+
+    /// Runs the callback bound to `key`, if any. `true` means a binding
+    /// handled it and built-in key handling should be skipped.
+    pub fn dispatch_key(&self, key: &str) -> mlua::Result<bool> {
+        let bindings: Table = self.lua.globals().get("__keybindings")?;
+        match bindings.get::<_, Option<Function>>(key)? {
+            Some(callback) => {
+                callback.call::<_, ()>(())?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Parses a raw command string into `Command`s replayable as keystrokes.
+
+use termion::event::Key;
+
+/// One step of a `Sequence`, e.g. `type foo` or `enter`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Type(String),
+    Enter,
+    Backspace,
+    Quit,
+}
+
+impl Command {
+    pub(crate) fn parse(raw: &str) -> Option<Command> {
+        let raw = raw.trim();
+        if let Some(text) = raw.strip_prefix("type ") {
+            return Some(Command::Type(text.to_string()));
+        }
+        match raw {
+            "enter" => Some(Command::Enter),
+            "backspace" => Some(Command::Backspace),
+            "quit" => Some(Command::Quit),
+            _ => None,
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Key> {
+        match self {
+            Command::Type(text) => text.chars().map(Key::Char).collect(),
+            Command::Enter => vec![Key::Char('\n')],
+            Command::Backspace => vec![Key::Backspace],
+            Command::Quit => vec![Key::Char('q')],
+        }
+    }
+}
+
+enum Repr {
+    /// A raw command string plus the separator that splits it into `Command`s.
+    Raw { raw: String, separator: char },
+    /// A single, already-parsed `Command`, immune to separator splitting.
+    Single(Command),
+}
+
+pub struct Sequence(Repr);
+
+impl Sequence {
+    pub fn new(raw: impl Into<String>) -> Sequence {
+        Sequence::with_separator(raw, ';')
+    }
+
+    pub fn with_separator(raw: impl Into<String>, separator: char) -> Sequence {
+        Sequence(Repr::Raw {
+            raw: raw.into(),
+            separator,
+        })
+    }
+
+    /// A sequence containing exactly `command`, taken as-is.
+    pub fn single(command: Command) -> Sequence {
+        Sequence(Repr::Single(command))
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        match &self.0 {
+            Repr::Raw { raw, separator } => {
+                raw.split(*separator).filter_map(Command::parse).collect()
+            }
+            Repr::Single(command) => vec![command.clone()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_command_kind() {
+        assert_eq!(Command::parse("type hello"), Some(Command::Type("hello".to_string())));
+        assert_eq!(Command::parse("enter"), Some(Command::Enter));
+        assert_eq!(Command::parse("backspace"), Some(Command::Backspace));
+        assert_eq!(Command::parse("quit"), Some(Command::Quit));
+        assert_eq!(Command::parse("nonsense"), None);
+    }
+
+    #[test]
+    fn splits_on_default_separator() {
+        let commands = Sequence::new("type foo; enter").commands();
+        assert_eq!(
+            commands,
+            vec![Command::Type("foo".to_string()), Command::Enter]
+        );
+    }
+
+    #[test]
+    fn splits_on_custom_separator() {
+        let commands = Sequence::with_separator("type foo|enter", '|').commands();
+        assert_eq!(
+            commands,
+            vec![Command::Type("foo".to_string()), Command::Enter]
+        );
+    }
+
+    #[test]
+    fn single_is_not_split_by_separator() {
+        let commands = Sequence::single(Command::Type("hello; world".to_string())).commands();
+        assert_eq!(commands, vec![Command::Type("hello; world".to_string())]);
+    }
+}
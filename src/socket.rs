@@ -0,0 +1,128 @@
+//! A Unix-domain-socket server for remote-controlling a running instance:
+//!
+//!   CMD type hello       run a single command
+//!   SEQ;type foo;enter   run a whole `Sequence`, separator right after `SEQ`
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::script::Scripting;
+use crate::sequence::{Command, Sequence};
+use crate::util::event::{CancellationToken, Renderer};
+use crate::{run_sequence, App};
+
+pub fn spawn_socket_server(
+    path: impl AsRef<Path>,
+    app: App,
+    scripting: Arc<Mutex<Scripting>>,
+    renderer: Renderer,
+    cancel: CancellationToken,
+) -> std::io::Result<thread::JoinHandle<()>> {
+    let path = path.as_ref().to_path_buf();
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            // One thread per connection, so a slow or idle controller can't
+            // stall the accept loop for every other one.
+            let app = app.clone();
+            let scripting = Arc::clone(&scripting);
+            let renderer = renderer.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || handle_connection(stream, &app, &scripting, &renderer, &cancel));
+        }
+    }))
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    app: &App,
+    scripting: &Mutex<Scripting>,
+    renderer: &Renderer,
+    cancel: &CancellationToken,
+) {
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+
+        let sequence = match parse_message(&line) {
+            Some(sequence) => sequence,
+            None => continue,
+        };
+
+        run_sequence(app, scripting, renderer, cancel, &sequence);
+        if cancel.is_cancelled() {
+            return;
+        }
+    }
+}
+
+/// Parses one `CMD <text>` or `SEQ<separator><text>` line into a `Sequence`.
+///
+/// `CMD` is the single-command form, so `text` is parsed as one `Command`
+/// directly rather than being split on a separator like `SEQ` is.
+fn parse_message(line: &str) -> Option<Sequence> {
+    if let Some(text) = line.strip_prefix("CMD ") {
+        return Some(Sequence::single(Command::parse(text)?));
+    }
+
+    let rest = line.strip_prefix("SEQ")?;
+    let mut chars = rest.chars();
+    let separator = chars.next()?;
+    Some(Sequence::with_separator(
+        chars.as_str().trim().to_string(),
+        separator,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmd_is_not_split_on_the_default_separator() {
+        let commands = parse_message("CMD type hello; world").unwrap().commands();
+        assert_eq!(commands, vec![Command::Type("hello; world".to_string())]);
+    }
+
+    #[test]
+    fn cmd_rejects_an_unparseable_command() {
+        assert!(parse_message("CMD nonsense").is_none());
+    }
+
+    #[test]
+    fn seq_splits_on_the_separator_right_after_seq() {
+        let commands = parse_message("SEQ;type foo;enter").unwrap().commands();
+        assert_eq!(
+            commands,
+            vec![Command::Type("foo".to_string()), Command::Enter]
+        );
+    }
+
+    #[test]
+    fn seq_honors_a_custom_separator() {
+        let commands = parse_message("SEQ|type foo|enter").unwrap().commands();
+        assert_eq!(
+            commands,
+            vec![Command::Type("foo".to_string()), Command::Enter]
+        );
+    }
+
+    #[test]
+    fn rejects_lines_without_a_known_prefix() {
+        assert!(parse_message("type hello").is_none());
+    }
+}
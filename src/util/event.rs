@@ -0,0 +1,105 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// How often `Events::next_timeout` wakes up to let a caller recheck a
+/// `CancellationToken` while no key has been pressed.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+use termion::event::Key;
+use termion::input::TermRead;
+
+/// A unit of work delivered to the dispatcher: a terminal key, or an
+/// internal `AppEvent`. Startup and socket sequences are replayed as
+/// `Input` keys; nothing constructs an `App` event.
+pub enum Event {
+    Input(Key),
+    #[allow(dead_code)]
+    App(AppEvent),
+}
+
+#[allow(dead_code)]
+pub enum AppEvent {
+    Tick,
+}
+
+/// What the dispatcher decided to do with an `Event`.
+pub enum EventStatus {
+    Ok,
+    Finished,
+    Terminate,
+}
+
+#[derive(Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Reads `Key` events from stdin on a dedicated thread.
+pub struct Events {
+    rx: mpsc::Receiver<Key>,
+}
+
+impl Events {
+    pub fn new() -> Events {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for key in stdin.keys().flatten() {
+                if tx.send(key).is_err() {
+                    return;
+                }
+            }
+        });
+        Events { rx }
+    }
+
+    /// Waits for the next key, waking up every `POLL_INTERVAL` with
+    /// `Err(Timeout)` if none arrives, so a caller can recheck a
+    /// `CancellationToken` set by another thread in between key presses.
+    pub fn next_timeout(&self) -> Result<Key, mpsc::RecvTimeoutError> {
+        self.rx.recv_timeout(POLL_INTERVAL)
+    }
+}
+
+/// Lets mutation sites ask the render thread to draw a new frame.
+#[derive(Clone)]
+pub struct Renderer {
+    tx: mpsc::Sender<()>,
+}
+
+impl Renderer {
+    pub fn request_redraw(&self) {
+        let _ = self.tx.send(());
+    }
+}
+
+/// Receiving half of the redraw channel, owned by the render loop.
+pub struct RedrawSignal {
+    rx: mpsc::Receiver<()>,
+}
+
+impl RedrawSignal {
+    pub fn wait(&self) -> Result<(), mpsc::RecvError> {
+        self.rx.recv()
+    }
+}
+
+pub fn redraw_channel() -> (Renderer, RedrawSignal) {
+    let (tx, rx) = mpsc::channel();
+    (Renderer { tx }, RedrawSignal { rx })
+}